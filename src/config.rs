@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::transport::proxy_protocol::ProxyProtocolVersion;
+
+const ALL_PROXY_ENV_NAME: &str = "ALL_PROXY";
+const HTTPS_PROXY_ENV_NAME: &str = "HTTPS_PROXY";
+const DEFAULT_MAX_CONNECTIONS: usize = 1024;
+const DEFAULT_SHUTDOWN_GRACE_PERIOD_SECONDS: u64 = 5;
+
+fn default_max_connections() -> usize {
+    DEFAULT_MAX_CONNECTIONS
+}
+
+fn default_shutdown_grace_period_seconds() -> u64 {
+    DEFAULT_SHUTDOWN_GRACE_PERIOD_SECONDS
+}
+/// Verified against an unknown username's credential check so that bcrypt's
+/// deliberately slow hashing runs the same amount of work on both the
+/// known-user and unknown-user paths, closing a username-enumeration timing
+/// side channel. Not a real credential; never matches any submitted password.
+const DUMMY_PASSWORD_HASH: &str = "$2y$10$N9qo8uLOickgx2ZMRZoMyeIjZAgcfl7p92ldGxad68LJZdL17lhWy";
+
+/// Connection details for an upstream HTTP CONNECT proxy that the agent
+/// should tunnel through before reaching the ppaass proxy.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProxyConfig {
+    host: String,
+    port: u16,
+    /// Pre-built `Proxy-Authorization` header value, e.g. `Basic <base64>`.
+    authorization: Option<String>,
+}
+
+impl UpstreamProxyConfig {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn authorization(&self) -> Option<&str> {
+        self.authorization.as_deref()
+    }
+
+    /// Fall back to reading `ALL_PROXY` / `HTTPS_PROXY` when no upstream
+    /// proxy was configured explicitly in the config file.
+    fn from_env() -> Option<Self> {
+        let proxy_url = env::var(ALL_PROXY_ENV_NAME)
+            .or_else(|_| env::var(HTTPS_PROXY_ENV_NAME))
+            .ok()?;
+        let parsed = url::Url::parse(&proxy_url).ok()?;
+        let host = parsed.host_str()?.to_string();
+        let port = parsed.port_or_known_default().unwrap_or(8080);
+        let authorization = if !parsed.username().is_empty() {
+            let credential = format!(
+                "{}:{}",
+                parsed.username(),
+                parsed.password().unwrap_or_default()
+            );
+            Some(format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(credential)
+            ))
+        } else {
+            None
+        };
+        Some(Self {
+            host,
+            port,
+            authorization,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentConfig {
+    port: u16,
+    ipv6: bool,
+    worker_thread_number: usize,
+    user_token: String,
+    proxy_host: String,
+    proxy_port: u16,
+    #[serde(default)]
+    upstream_proxy: Option<UpstreamProxyConfig>,
+    /// Username -> bcrypt password hash, required from every SOCKS5/HTTP
+    /// client before a proxy connection is created on its behalf.
+    #[serde(default)]
+    user_credentials: HashMap<String, String>,
+    /// PROXY protocol preamble written to the proxy connection so the
+    /// downstream proxy learns the real client address.
+    #[serde(default)]
+    proxy_protocol_version: ProxyProtocolVersion,
+    /// When set, look up the target host at the agent (override table,
+    /// then system resolver) instead of deferring resolution to the proxy.
+    #[serde(default)]
+    resolve_at_agent: bool,
+    /// Private hosts file consulted before the system resolver when
+    /// `resolve_at_agent` is enabled.
+    #[serde(default)]
+    static_dns_overrides: HashMap<String, IpAddr>,
+    /// Maximum number of client connections handled concurrently; further
+    /// accepted sockets wait for a permit to free up. Defaults to 1024 so
+    /// configs written before this option existed keep working.
+    #[serde(default = "default_max_connections")]
+    max_connections: usize,
+    /// How long `AgentServerGuard::shutdown` waits for in-flight transports
+    /// to drain before giving up. Defaults to 5 seconds.
+    #[serde(default = "default_shutdown_grace_period_seconds")]
+    shutdown_grace_period_seconds: u64,
+    /// Bind address for the metrics HTTP endpoint (JSON/Prometheus
+    /// snapshot). Metrics collection still runs, and is still logged
+    /// periodically, even when this is unset.
+    #[serde(default)]
+    metrics_bind_address: Option<String>,
+}
+
+impl AgentConfig {
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn proxy_host(&self) -> &str {
+        &self.proxy_host
+    }
+
+    pub fn proxy_port(&self) -> u16 {
+        self.proxy_port
+    }
+
+    pub fn ipv6(&self) -> bool {
+        self.ipv6
+    }
+
+    pub fn worker_thread_number(&self) -> usize {
+        self.worker_thread_number
+    }
+
+    pub fn user_token(&self) -> &str {
+        &self.user_token
+    }
+
+    /// The upstream HTTP CONNECT proxy set in the config file, if any. The
+    /// `ALL_PROXY` / `HTTPS_PROXY` fallback is not applied here; the proxy
+    /// connection factory uses `resolve_upstream_proxy` for that.
+    pub fn upstream_proxy(&self) -> Option<&UpstreamProxyConfig> {
+        self.upstream_proxy.as_ref()
+    }
+
+    pub(crate) fn resolve_upstream_proxy(&self) -> Option<UpstreamProxyConfig> {
+        self.upstream_proxy
+            .clone()
+            .or_else(UpstreamProxyConfig::from_env)
+    }
+
+    pub fn proxy_protocol_version(&self) -> ProxyProtocolVersion {
+        self.proxy_protocol_version
+    }
+
+    pub fn max_connections(&self) -> usize {
+        self.max_connections
+    }
+
+    pub fn shutdown_grace_period_seconds(&self) -> u64 {
+        self.shutdown_grace_period_seconds
+    }
+
+    pub fn metrics_bind_address(&self) -> Option<&str> {
+        self.metrics_bind_address.as_deref()
+    }
+
+    pub fn resolve_at_agent(&self) -> bool {
+        self.resolve_at_agent
+    }
+
+    pub fn static_dns_overrides(&self) -> &HashMap<String, IpAddr> {
+        &self.static_dns_overrides
+    }
+
+    /// Verifies a username/password pair against the configured bcrypt
+    /// hashes. Returns `false` for unknown users instead of erroring so a
+    /// bad username can't be distinguished from a bad password. Unknown
+    /// usernames still pay the full bcrypt verification cost against a
+    /// dummy hash, so the response time doesn't leak which usernames exist.
+    pub(crate) fn verify_client_credential(&self, username: &str, password: &str) -> bool {
+        match self.user_credentials.get(username) {
+            Some(password_hash) => bcrypt::verify(password, password_hash).unwrap_or(false),
+            None => {
+                let _ = bcrypt::verify(password, DUMMY_PASSWORD_HASH);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use ppaass_crypto::crypto::{RsaCrypto, RsaCryptoFetcher};
+use ppaass_protocol::message::values::encryption::{
+    PpaassMessagePayloadEncryption, PpaassMessagePayloadEncryptionSelector,
+};
+
+use crate::{config::AgentConfig, error::AgentError};
+
+/// Loads the agent's RSA key pair once at startup and hands it back out for
+/// every proxy message that needs to be encrypted or decrypted.
+pub(crate) struct AgentServerRsaCryptoFetcher {
+    rsa_crypto: RsaCrypto,
+}
+
+impl AgentServerRsaCryptoFetcher {
+    pub(crate) fn new(config: &AgentConfig) -> Result<Self, AgentError> {
+        let rsa_crypto = RsaCrypto::new(config.user_token())?;
+        Ok(Self { rsa_crypto })
+    }
+}
+
+impl RsaCryptoFetcher for AgentServerRsaCryptoFetcher {
+    fn fetch(&self, _user_token: impl AsRef<str>) -> Result<Option<&RsaCrypto>, ppaass_crypto::error::CryptoError> {
+        Ok(Some(&self.rsa_crypto))
+    }
+}
+
+pub(crate) struct AgentServerPayloadEncryptionTypeSelector;
+
+impl PpaassMessagePayloadEncryptionSelector for AgentServerPayloadEncryptionTypeSelector {
+    fn select(_user_token: impl AsRef<str>, encryption_token: Option<Vec<u8>>) -> PpaassMessagePayloadEncryption {
+        PpaassMessagePayloadEncryption::Aes(Arc::new(encryption_token.unwrap_or_default()).to_vec())
+    }
+}
@@ -0,0 +1,19 @@
+use std::io;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AgentError {
+    #[error("agent io error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("agent rsa crypto error: {0}")]
+    Rsa(#[from] ppaass_crypto::error::CryptoError),
+
+    #[error("agent protocol error: {0}")]
+    Protocol(#[from] ppaass_protocol::error::ProtocolError),
+
+    #[error("agent configuration error: {0}")]
+    Config(String),
+
+    #[error("{0}")]
+    Other(String),
+}
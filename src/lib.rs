@@ -0,0 +1,12 @@
+pub(crate) mod config;
+pub(crate) mod crypto;
+pub(crate) mod error;
+pub(crate) mod metrics;
+pub(crate) mod proxy;
+pub(crate) mod resolver;
+mod server;
+pub(crate) mod transport;
+
+pub use config::AgentConfig;
+pub use error::AgentError;
+pub use server::{AgentServer, AgentServerGuard};
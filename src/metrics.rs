@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::error::AgentError;
+
+const METRICS_LOG_INTERVAL: Duration = Duration::from_secs(60);
+const PROMETHEUS_PATH: &str = "/metrics";
+const REQUEST_LINE_BUFFER_SIZE: usize = 1024;
+
+struct TunnelMetricsEntry {
+    src_address: String,
+    upload_bytes_amount: Arc<AtomicU64>,
+    download_bytes_amount: Arc<AtomicU64>,
+}
+
+#[derive(Serialize)]
+struct TunnelMetricsSnapshot {
+    transport_id: String,
+    src_address: String,
+    upload_bytes: u64,
+    download_bytes: u64,
+}
+
+#[derive(Serialize)]
+struct MetricsSnapshot {
+    active_connections: usize,
+    upload_bytes_total: u64,
+    download_bytes_total: u64,
+    tunnels: Vec<TunnelMetricsSnapshot>,
+}
+
+/// Tracks per-tunnel upload/download byte counters plus the live connection
+/// count, keyed by `transport_id`. Registered when a transport finishes
+/// initializing a proxy tunnel, deregistered once it finishes serving.
+#[derive(Default)]
+pub(crate) struct MetricsRegistry {
+    tunnels: RwLock<HashMap<String, TunnelMetricsEntry>>,
+}
+
+impl MetricsRegistry {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a new tunnel and returns the upload/download counters the
+    /// caller should increment as bytes are relayed.
+    pub(crate) fn register(
+        &self,
+        transport_id: String,
+        src_address: String,
+    ) -> (Arc<AtomicU64>, Arc<AtomicU64>) {
+        let upload_bytes_amount = Arc::new(AtomicU64::new(0));
+        let download_bytes_amount = Arc::new(AtomicU64::new(0));
+        self.tunnels.write().unwrap().insert(
+            transport_id,
+            TunnelMetricsEntry {
+                src_address,
+                upload_bytes_amount: upload_bytes_amount.clone(),
+                download_bytes_amount: download_bytes_amount.clone(),
+            },
+        );
+        (upload_bytes_amount, download_bytes_amount)
+    }
+
+    pub(crate) fn deregister(&self, transport_id: &str) {
+        self.tunnels.write().unwrap().remove(transport_id);
+    }
+
+    fn snapshot(&self) -> MetricsSnapshot {
+        let tunnels = self.tunnels.read().unwrap();
+        let mut upload_bytes_total = 0u64;
+        let mut download_bytes_total = 0u64;
+        let tunnel_snapshots = tunnels
+            .iter()
+            .map(|(transport_id, entry)| {
+                let upload_bytes = entry.upload_bytes_amount.load(Ordering::Relaxed);
+                let download_bytes = entry.download_bytes_amount.load(Ordering::Relaxed);
+                upload_bytes_total += upload_bytes;
+                download_bytes_total += download_bytes;
+                TunnelMetricsSnapshot {
+                    transport_id: transport_id.clone(),
+                    src_address: entry.src_address.clone(),
+                    upload_bytes,
+                    download_bytes,
+                }
+            })
+            .collect();
+        MetricsSnapshot {
+            active_connections: tunnels.len(),
+            upload_bytes_total,
+            download_bytes_total,
+            tunnels: tunnel_snapshots,
+        }
+    }
+
+    fn render_prometheus(&self) -> String {
+        let snapshot = self.snapshot();
+        format!(
+            "# TYPE agent_upload_bytes_total counter\nagent_upload_bytes_total {}\n# TYPE agent_download_bytes_total counter\nagent_download_bytes_total {}\n# TYPE agent_active_connections gauge\nagent_active_connections {}\n",
+            snapshot.upload_bytes_total, snapshot.download_bytes_total, snapshot.active_connections
+        )
+    }
+}
+
+/// Logs an aggregate throughput summary on a fixed interval.
+pub(crate) fn spawn_periodic_summary_logger(registry: Arc<MetricsRegistry>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(METRICS_LOG_INTERVAL);
+        loop {
+            interval.tick().await;
+            let snapshot = registry.snapshot();
+            info!(
+                "Agent metrics summary: {} active connections, {} bytes uploaded, {} bytes downloaded.",
+                snapshot.active_connections, snapshot.upload_bytes_total, snapshot.download_bytes_total
+            );
+        }
+    });
+}
+
+/// Serves a metrics snapshot over plain HTTP: `GET /metrics` returns
+/// Prometheus text exposition format, anything else returns JSON.
+pub(crate) async fn serve_metrics_endpoint(
+    bind_addr: SocketAddr,
+    registry: Arc<MetricsRegistry>,
+) -> Result<(), AgentError> {
+    let listener = TcpListener::bind(bind_addr).await?;
+    info!("Agent metrics endpoint start to serve request on address: {bind_addr}.");
+    loop {
+        let (mut client_tcp_stream, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            let mut request_buf = [0u8; REQUEST_LINE_BUFFER_SIZE];
+            let read_size = match client_tcp_stream.read(&mut request_buf).await {
+                Ok(size) => size,
+                Err(e) => {
+                    warn!("Agent metrics endpoint fail to read request because of error: {e:?}");
+                    return;
+                }
+            };
+            let request_line = String::from_utf8_lossy(&request_buf[..read_size]);
+            let request_path = request_line
+                .split_whitespace()
+                .nth(1)
+                .unwrap_or(PROMETHEUS_PATH);
+            let body = if request_path.starts_with(PROMETHEUS_PATH) {
+                registry.render_prometheus()
+            } else {
+                serde_json::to_string(&registry.snapshot()).unwrap_or_default()
+            };
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            if let Err(e) = client_tcp_stream.write_all(response.as_bytes()).await {
+                warn!("Agent metrics endpoint fail to write response because of error: {e:?}");
+            }
+        });
+    }
+}
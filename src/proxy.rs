@@ -0,0 +1,162 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use futures::stream::{SplitSink, SplitStream};
+use ppaass_codec::codec::PpaassProxyEdgeCodec;
+use ppaass_crypto::crypto::RsaCryptoFetcher;
+use ppaass_protocol::message::{PpaassAgentMessage, PpaassProxyMessage};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{lookup_host, TcpStream};
+use tokio_util::codec::Framed;
+use tracing::debug;
+
+use crate::{
+    config::{AgentConfig, UpstreamProxyConfig},
+    error::AgentError,
+};
+
+const CONNECT_OK_STATUS: &str = " 200 ";
+const MAX_RESPONSE_LINE_LEN: usize = 4096;
+
+pub(crate) type ProxyConnection<F> = Framed<TcpStream, PpaassProxyEdgeCodec<F>>;
+pub(crate) type ProxyConnectionWrite<F> = SplitSink<ProxyConnection<F>, PpaassAgentMessage>;
+pub(crate) type ProxyConnectionRead<F> = SplitStream<ProxyConnection<F>>;
+
+/// Builds the tcp connection used to talk to the ppaass proxy, optionally
+/// tunneling through an upstream HTTP CONNECT proxy first.
+pub(crate) struct ProxyConnectionFactory<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    config: Arc<AgentConfig>,
+    rsa_crypto_fetcher: Arc<F>,
+}
+
+impl<F> ProxyConnectionFactory<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    pub(crate) fn new(config: Arc<AgentConfig>, rsa_crypto_fetcher: F) -> Result<Self, AgentError> {
+        Ok(Self {
+            config,
+            rsa_crypto_fetcher: Arc::new(rsa_crypto_fetcher),
+        })
+    }
+
+    pub(crate) async fn create_proxy_connection(&self) -> Result<ProxyConnection<F>, AgentError> {
+        let proxy_host = self.config.proxy_host();
+        let proxy_port = self.config.proxy_port();
+        let proxy_tcp_stream = match self.config.resolve_upstream_proxy() {
+            Some(upstream_proxy) => {
+                debug!(
+                    "Tunnel to ppaass proxy [{proxy_host}:{proxy_port}] through upstream proxy [{}:{}]",
+                    upstream_proxy.host(),
+                    upstream_proxy.port()
+                );
+                Self::connect_through_upstream_proxy(&upstream_proxy, proxy_host, proxy_port).await?
+            }
+            None => TcpStream::connect((proxy_host, proxy_port)).await?,
+        };
+        proxy_tcp_stream.set_nodelay(true)?;
+        Ok(Framed::new(
+            proxy_tcp_stream,
+            PpaassProxyEdgeCodec::new(self.rsa_crypto_fetcher.clone()),
+        ))
+    }
+
+    /// Connects to the upstream proxy, issues an IPv6-safe `CONNECT` for the
+    /// ppaass proxy address, and returns the tunneled tcp stream once the
+    /// upstream has replied with a `200` status line.
+    async fn connect_through_upstream_proxy(
+        upstream_proxy: &UpstreamProxyConfig,
+        target_host: &str,
+        target_port: u16,
+    ) -> Result<TcpStream, AgentError> {
+        let mut upstream_tcp_stream =
+            TcpStream::connect((upstream_proxy.host(), upstream_proxy.port())).await?;
+        upstream_tcp_stream.set_nodelay(true)?;
+
+        let authority = build_connect_authority(target_host, target_port);
+        let mut connect_request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some(authorization) = upstream_proxy.authorization() {
+            connect_request.push_str(&format!("Proxy-Authorization: {authorization}\r\n"));
+        }
+        connect_request.push_str("\r\n");
+        upstream_tcp_stream
+            .write_all(connect_request.as_bytes())
+            .await?;
+
+        let status_line = read_response_line(&mut upstream_tcp_stream).await?;
+        if !status_line.contains(CONNECT_OK_STATUS) {
+            return Err(AgentError::Other(format!(
+                "Upstream proxy [{}:{}] refuse to connect [{authority}], response status line: {status_line}",
+                upstream_proxy.host(),
+                upstream_proxy.port()
+            )));
+        }
+        // Drain the remaining response headers up to the blank line that
+        // separates them from the tunneled bytes.
+        loop {
+            let header_line = read_response_line(&mut upstream_tcp_stream).await?;
+            if header_line.is_empty() {
+                break;
+            }
+        }
+        Ok(upstream_tcp_stream)
+    }
+}
+
+/// Resolves the real ppaass proxy's address, for use as the PROXY protocol
+/// preamble's `dst` — distinct from the proxy connection's actual TCP peer,
+/// which is the upstream CONNECT proxy rather than the ppaass proxy when an
+/// upstream proxy is configured.
+pub(crate) async fn resolve_ppaass_proxy_addr(config: &AgentConfig) -> Result<SocketAddr, AgentError> {
+    lookup_host((config.proxy_host(), config.proxy_port()))
+        .await?
+        .next()
+        .ok_or_else(|| {
+            AgentError::Other(format!(
+                "Fail to resolve ppaass proxy address [{}:{}]",
+                config.proxy_host(),
+                config.proxy_port()
+            ))
+        })
+}
+
+/// Wraps `host` in `[...]` when it is an IPv6 literal so it can be used as a
+/// `CONNECT` authority (`host:port`).
+fn build_connect_authority(host: &str, port: u16) -> String {
+    if host.contains(':') && !host.starts_with('[') {
+        format!("[{host}]:{port}")
+    } else {
+        format!("{host}:{port}")
+    }
+}
+
+/// Reads a single `\r\n`-terminated line from the upstream proxy's CONNECT
+/// response one byte at a time so no bytes belonging to the tunneled
+/// connection are ever buffered away.
+async fn read_response_line(stream: &mut TcpStream) -> Result<String, AgentError> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        stream.read_exact(&mut byte).await.map_err(|e| {
+            AgentError::Other(format!(
+                "Upstream proxy closed connection while reading CONNECT response: {e:?}"
+            ))
+        })?;
+        if byte[0] == b'\n' {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            break;
+        }
+        line.push(byte[0]);
+        if line.len() > MAX_RESPONSE_LINE_LEN {
+            return Err(AgentError::Other(
+                "Upstream proxy CONNECT response line too long".to_string(),
+            ));
+        }
+    }
+    Ok(String::from_utf8_lossy(&line).to_string())
+}
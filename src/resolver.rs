@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use async_trait::async_trait;
+use hickory_resolver::TokioAsyncResolver;
+
+use crate::{config::AgentConfig, error::AgentError};
+
+/// A source of host -> IP lookups. Implementations may answer from a local
+/// table, a full DNS resolver, or anything else — callers only care that a
+/// host either resolves to a concrete address or doesn't.
+#[async_trait]
+pub(crate) trait DnsResolver: Send + Sync {
+    async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, AgentError>;
+}
+
+/// A private hosts file: exact hostname matches only, no wildcards.
+pub(crate) struct StaticOverrideResolver {
+    overrides: HashMap<String, IpAddr>,
+}
+
+impl StaticOverrideResolver {
+    pub(crate) fn new(overrides: HashMap<String, IpAddr>) -> Self {
+        Self { overrides }
+    }
+}
+
+#[async_trait]
+impl DnsResolver for StaticOverrideResolver {
+    async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, AgentError> {
+        Ok(self.overrides.get(host).copied())
+    }
+}
+
+/// Falls back to the system's configured DNS servers (`/etc/resolv.conf`
+/// and friends).
+pub(crate) struct SystemDnsResolver {
+    inner: TokioAsyncResolver,
+}
+
+impl SystemDnsResolver {
+    pub(crate) fn from_system_conf() -> Result<Self, AgentError> {
+        let inner = TokioAsyncResolver::tokio_from_system_conf()
+            .map_err(|e| AgentError::Other(format!("Fail to load system dns configuration: {e:?}")))?;
+        Ok(Self { inner })
+    }
+}
+
+#[async_trait]
+impl DnsResolver for SystemDnsResolver {
+    async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, AgentError> {
+        let lookup = match self.inner.lookup_ip(host).await {
+            Ok(lookup) => lookup,
+            Err(_) => return Ok(None),
+        };
+        Ok(lookup.iter().next())
+    }
+}
+
+/// Resolves a target host at the agent instead of deferring to the proxy:
+/// the static override table is always checked first so operators can pin
+/// or split-horizon individual hosts, then the system resolver is tried.
+pub(crate) struct AgentResolver {
+    static_override_resolver: StaticOverrideResolver,
+    /// Only built when `resolve_at_agent` is enabled: `TokioAsyncResolver`
+    /// spawns its background driver on construction and needs a Tokio
+    /// runtime already entered, so it must not be built unconditionally at
+    /// startup.
+    system_resolver: Option<SystemDnsResolver>,
+}
+
+impl AgentResolver {
+    pub(crate) fn new(config: &AgentConfig) -> Result<Self, AgentError> {
+        let system_resolver = if config.resolve_at_agent() {
+            Some(SystemDnsResolver::from_system_conf()?)
+        } else {
+            None
+        };
+        Ok(Self {
+            static_override_resolver: StaticOverrideResolver::new(
+                config.static_dns_overrides().clone(),
+            ),
+            system_resolver,
+        })
+    }
+
+    /// Returns `None` when neither the override table nor the system
+    /// resolver can answer, so the caller can fall back to leaving the
+    /// destination as a `Domain` address for the proxy to resolve.
+    pub(crate) async fn resolve(&self, host: &str) -> Result<Option<IpAddr>, AgentError> {
+        if let Some(ip_addr) = self.static_override_resolver.resolve(host).await? {
+            return Ok(Some(ip_addr));
+        }
+        match &self.system_resolver {
+            Some(system_resolver) => system_resolver.resolve(host).await,
+            None => Ok(None),
+        }
+    }
+}
@@ -4,11 +4,14 @@ use std::time::Duration;
 use crate::{config::AgentConfig, error::AgentError};
 use crate::{
     crypto::AgentServerRsaCryptoFetcher,
+    metrics::{self, MetricsRegistry},
     proxy::ProxyConnectionFactory,
+    resolver::AgentResolver,
     transport::dispatcher::{ClientTransport, ClientTransportDispatcher},
 };
 use tokio::net::{TcpListener, TcpStream};
 use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{watch, OwnedSemaphorePermit, Semaphore};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
 use tracing::{debug, error, info};
@@ -18,6 +21,10 @@ const AGENT_SERVER_RUNTIME_NAME: &str = "AGENT-SERVER";
 pub struct AgentServerGuard {
     join_handle: JoinHandle<()>,
     runtime: Runtime,
+    shutdown_tx: watch::Sender<bool>,
+    connection_semaphore: Arc<Semaphore>,
+    max_connections: usize,
+    shutdown_grace_period: Duration,
 }
 
 impl AgentServerGuard {
@@ -28,12 +35,42 @@ impl AgentServerGuard {
             }
         });
     }
+
+    /// Stops the accept loop from taking new connections, then waits up to
+    /// the configured grace period for all in-flight transports to drain
+    /// before returning.
+    ///
+    /// The accept loop task is aborted as well as signalled: while it awaits
+    /// `accept()` it sits outside the `select!` that observes the shutdown
+    /// signal and holds a connection permit of its own, which the drain
+    /// below would otherwise wait on until the next client connected.
+    /// In-flight transports run in their own tasks and are not aborted.
+    pub fn shutdown(&self) {
+        let _ = self.shutdown_tx.send(true);
+        self.join_handle.abort();
+        self.runtime.block_on(async {
+            let drain_all_connections =
+                self.connection_semaphore.acquire_many(self.max_connections as u32);
+            if tokio::time::timeout(self.shutdown_grace_period, drain_all_connections)
+                .await
+                .is_err()
+            {
+                error!(
+                    "Agent server fail to drain all in-flight connections within grace period {:?}, shutdown anyway.",
+                    self.shutdown_grace_period
+                );
+            }
+        });
+    }
 }
 
 pub struct AgentServer {
     config: Arc<AgentConfig>,
     runtime: Runtime,
     client_transport_dispatcher: Arc<ClientTransportDispatcher<AgentServerRsaCryptoFetcher>>,
+    connection_semaphore: Arc<Semaphore>,
+    shutdown_tx: watch::Sender<bool>,
+    metrics_registry: Arc<MetricsRegistry>,
 }
 
 impl AgentServer {
@@ -42,17 +79,30 @@ impl AgentServer {
         let rsa_crypto_fetcher = AgentServerRsaCryptoFetcher::new(&config)?;
         let proxy_connection_factory =
             ProxyConnectionFactory::new(config.clone(), rsa_crypto_fetcher)?;
-        let client_transport_dispatcher =
-            ClientTransportDispatcher::new(config.clone(), proxy_connection_factory);
+        let metrics_registry = Arc::new(MetricsRegistry::new());
         let runtime = Builder::new_multi_thread()
             .enable_all()
             .thread_name(AGENT_SERVER_RUNTIME_NAME)
             .worker_threads(config.worker_thread_number())
             .build()?;
+        // `AgentResolver` may build a `TokioAsyncResolver`, which spawns its
+        // background driver on construction and therefore needs a Tokio
+        // runtime already entered; build it only after `runtime` exists.
+        let agent_resolver = Arc::new(runtime.block_on(async { AgentResolver::new(&config) })?);
+        let client_transport_dispatcher = ClientTransportDispatcher::new(
+            config.clone(),
+            proxy_connection_factory,
+            agent_resolver,
+            metrics_registry.clone(),
+        );
+        let (shutdown_tx, _) = watch::channel(false);
         Ok(Self {
+            connection_semaphore: Arc::new(Semaphore::new(config.max_connections())),
+            shutdown_tx,
             config,
             runtime,
             client_transport_dispatcher: Arc::new(client_transport_dispatcher),
+            metrics_registry,
         })
     }
     async fn accept_client_connection(
@@ -66,6 +116,8 @@ impl AgentServer {
     async fn run(
         config: Arc<AgentConfig>,
         client_transport_dispatcher: Arc<ClientTransportDispatcher<AgentServerRsaCryptoFetcher>>,
+        connection_semaphore: Arc<Semaphore>,
+        mut shutdown_rx: watch::Receiver<bool>,
     ) -> Result<(), AgentError> {
         let agent_server_bind_addr = if config.ipv6() {
             format!("::1:{}", config.port())
@@ -75,6 +127,20 @@ impl AgentServer {
         info!("Agent server start to serve request on address: {agent_server_bind_addr}.");
         let tcp_listener = TcpListener::bind(&agent_server_bind_addr).await?;
         loop {
+            // Acquiring the permit before accepting applies back-pressure on
+            // the accept loop itself once `max_connections` is saturated.
+            let permit = tokio::select! {
+                _ = shutdown_rx.changed() => {
+                    info!("Agent server stop accepting new connections.");
+                    return Ok(());
+                }
+                permit_result = connection_semaphore.clone().acquire_owned() => {
+                    match permit_result {
+                        Ok(permit) => permit,
+                        Err(_) => return Ok(()),
+                    }
+                }
+            };
             match Self::accept_client_connection(&tcp_listener).await {
                 Ok((client_tcp_stream, client_socket_address)) => {
                     debug!("Accept client tcp connection on address: {client_socket_address}");
@@ -82,9 +148,11 @@ impl AgentServer {
                         client_tcp_stream,
                         client_socket_address,
                         client_transport_dispatcher.clone(),
+                        permit,
                     );
                 }
                 Err(e) => {
+                    drop(permit);
                     error!("Agent server fail to accept client connection because of error: {e:?}");
                     continue;
                 }
@@ -93,14 +161,51 @@ impl AgentServer {
     }
 
     pub fn start(self) -> AgentServerGuard {
+        let connection_semaphore = self.connection_semaphore.clone();
+        let shutdown_rx = self.shutdown_tx.subscribe();
+        let max_connections = self.config.max_connections();
+        let shutdown_grace_period = Duration::from_secs(self.config.shutdown_grace_period_seconds());
+        {
+            let _runtime_guard = self.runtime.enter();
+            metrics::spawn_periodic_summary_logger(self.metrics_registry.clone());
+        }
+        if let Some(metrics_bind_address) = self.config.metrics_bind_address() {
+            match metrics_bind_address.parse::<SocketAddr>() {
+                Ok(metrics_bind_address) => {
+                    let metrics_registry = self.metrics_registry.clone();
+                    self.runtime.spawn(async move {
+                        if let Err(e) =
+                            metrics::serve_metrics_endpoint(metrics_bind_address, metrics_registry)
+                                .await
+                        {
+                            error!("Agent metrics endpoint fail to serve because of error: {e:?}");
+                        }
+                    });
+                }
+                Err(e) => {
+                    error!("Agent metrics endpoint bind address [{metrics_bind_address}] is invalid because of error: {e:?}");
+                }
+            }
+        }
         let join_handle = self.runtime.spawn(async move {
-            if let Err(e) = Self::run(self.config, self.client_transport_dispatcher).await {
+            if let Err(e) = Self::run(
+                self.config,
+                self.client_transport_dispatcher,
+                self.connection_semaphore,
+                shutdown_rx,
+            )
+            .await
+            {
                 error!("Fail to start agent server because of error: {e:?}");
             }
         });
         AgentServerGuard {
             join_handle,
             runtime: self.runtime,
+            shutdown_tx: self.shutdown_tx,
+            connection_semaphore,
+            max_connections,
+            shutdown_grace_period,
         }
     }
 
@@ -108,8 +213,10 @@ impl AgentServer {
         client_tcp_stream: TcpStream,
         client_socket_address: SocketAddr,
         client_transport_dispatcher: Arc<ClientTransportDispatcher<AgentServerRsaCryptoFetcher>>,
+        connection_permit: OwnedSemaphorePermit,
     ) {
         tokio::spawn(async move {
+            let _connection_permit = connection_permit;
             let client_transport = client_transport_dispatcher
                 .dispatch(client_tcp_stream, client_socket_address)
                 .await?;
@@ -0,0 +1,94 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bytes::BytesMut;
+use ppaass_crypto::crypto::RsaCryptoFetcher;
+use ppaass_protocol::message::values::address::PpaassUnifiedAddress;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+use crate::{
+    config::AgentConfig,
+    error::AgentError,
+    metrics::MetricsRegistry,
+    proxy::ProxyConnectionFactory,
+    resolver::AgentResolver,
+    transport::{http::HttpClientTransport, socks5::Socks5ClientTransport},
+};
+
+const SOCKS5_VERSION: u8 = 0x05;
+
+pub(crate) enum ClientTransport<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    Socks5(Socks5ClientTransport<F>),
+    Http(HttpClientTransport<F>),
+}
+
+/// Peeks at the first byte on a freshly accepted client socket to decide
+/// whether it is speaking SOCKS5 or plain/CONNECT HTTP, then builds the
+/// matching transport.
+pub(crate) struct ClientTransportDispatcher<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    config: Arc<AgentConfig>,
+    proxy_connection_factory: Arc<ProxyConnectionFactory<F>>,
+    resolver: Arc<AgentResolver>,
+    metrics_registry: Arc<MetricsRegistry>,
+}
+
+impl<F> ClientTransportDispatcher<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    pub(crate) fn new(
+        config: Arc<AgentConfig>,
+        proxy_connection_factory: ProxyConnectionFactory<F>,
+        resolver: Arc<AgentResolver>,
+        metrics_registry: Arc<MetricsRegistry>,
+    ) -> Self {
+        Self {
+            config,
+            proxy_connection_factory: Arc::new(proxy_connection_factory),
+            resolver,
+            metrics_registry,
+        }
+    }
+
+    pub(crate) async fn dispatch(
+        &self,
+        mut client_tcp_stream: TcpStream,
+        client_socket_address: SocketAddr,
+    ) -> Result<ClientTransport<F>, AgentError> {
+        let mut protocol_byte = [0u8; 1];
+        client_tcp_stream.peek(&mut protocol_byte).await?;
+        let src_address = PpaassUnifiedAddress::from(client_socket_address);
+        if protocol_byte[0] == SOCKS5_VERSION {
+            let mut initial_buf = BytesMut::with_capacity(1);
+            client_tcp_stream.read_buf(&mut initial_buf).await?;
+            Ok(ClientTransport::Socks5(Socks5ClientTransport::new(
+                client_tcp_stream,
+                src_address,
+                initial_buf,
+                client_socket_address,
+                self.config.clone(),
+                self.proxy_connection_factory.clone(),
+                self.resolver.clone(),
+                self.metrics_registry.clone(),
+            )))
+        } else {
+            Ok(ClientTransport::Http(HttpClientTransport::new(
+                client_tcp_stream,
+                src_address,
+                BytesMut::new(),
+                client_socket_address,
+                self.config.clone(),
+                self.proxy_connection_factory.clone(),
+                self.resolver.clone(),
+                self.metrics_registry.clone(),
+            )))
+        }
+    }
+}
@@ -4,16 +4,20 @@ use bytecodec::{bytes::BytesEncoder, EncodeExt};
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use base64::Engine;
 use bytes::{Bytes, BytesMut};
 
 use futures::{SinkExt, StreamExt};
-use httpcodec::{BodyEncoder, HttpVersion, ReasonPhrase, RequestEncoder, Response, StatusCode};
+use httpcodec::{
+    BodyEncoder, HttpVersion, ReasonPhrase, Request, RequestEncoder, Response, StatusCode,
+};
 use ppaass_crypto::{crypto::RsaCryptoFetcher, random_32_bytes};
 use ppaass_protocol::generator::PpaassMessageGenerator;
 use ppaass_protocol::message::payload::tcp::{ProxyTcpInitResult, ProxyTcpPayload};
 use ppaass_protocol::message::values::address::PpaassUnifiedAddress;
 use ppaass_protocol::message::values::encryption::PpaassMessagePayloadEncryptionSelector;
 use ppaass_protocol::message::{PpaassProxyMessage, PpaassProxyMessagePayload};
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
 
 use tokio_util::codec::{Framed, FramedParts};
@@ -22,12 +26,18 @@ use url::Url;
 
 use crate::{
     config::AgentConfig, crypto::AgentServerPayloadEncryptionTypeSelector,
-    proxy::ProxyConnectionFactory,
+    proxy::{resolve_ppaass_proxy_addr, ProxyConnectionFactory},
 };
 
 use crate::{
     error::AgentError,
-    transport::{http::codec::HttpCodec, ClientTransportTcpDataRelay},
+    metrics::MetricsRegistry,
+    resolver::AgentResolver,
+    transport::{
+        http::codec::HttpCodec,
+        proxy_protocol::{build_preamble, ProxyProtocolVersion},
+        ClientTransportTcpDataRelay,
+    },
 };
 
 use super::tcp_relay;
@@ -39,6 +49,29 @@ const HTTPS_DEFAULT_PORT: u16 = 443;
 const HTTP_DEFAULT_PORT: u16 = 80;
 const OK_CODE: u16 = 200;
 const CONNECTION_ESTABLISHED: &str = "Connection Established";
+const PROXY_AUTH_REQUIRED_CODE: u16 = 407;
+const PROXY_AUTH_REQUIRED_REASON: &str = "Proxy Authentication Required";
+const PROXY_AUTHORIZATION_HEADER: &str = "Proxy-Authorization";
+const PROXY_AUTHENTICATE_HEADER: &str = "Proxy-Authenticate";
+const BASIC_AUTH_SCHEME: &str = "Basic";
+
+/// Pulls the username/password pair out of a `Proxy-Authorization: Basic
+/// ...` header, if present and well-formed.
+fn extract_basic_credential<T>(http_message: &Request<T>) -> Option<(String, String)> {
+    let header_value = http_message
+        .header()
+        .fields()
+        .find(|field| field.name().eq_ignore_ascii_case(PROXY_AUTHORIZATION_HEADER))?
+        .value()
+        .to_string();
+    let encoded_credential = header_value.strip_prefix(BASIC_AUTH_SCHEME)?.trim();
+    let decoded_credential = base64::engine::general_purpose::STANDARD
+        .decode(encoded_credential)
+        .ok()?;
+    let decoded_credential = String::from_utf8(decoded_credential).ok()?;
+    let (username, password) = decoded_credential.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
 
 pub(crate) struct HttpClientTransport<F>
 where
@@ -50,6 +83,8 @@ where
     client_socket_addr: SocketAddr,
     config: Arc<AgentConfig>,
     proxy_connection_factory: Arc<ProxyConnectionFactory<F>>,
+    resolver: Arc<AgentResolver>,
+    metrics_registry: Arc<MetricsRegistry>,
 }
 
 impl<F> HttpClientTransport<F>
@@ -63,6 +98,8 @@ where
         client_socket_addr: SocketAddr,
         config: Arc<AgentConfig>,
         proxy_connection_factory: Arc<ProxyConnectionFactory<F>>,
+        resolver: Arc<AgentResolver>,
+        metrics_registry: Arc<MetricsRegistry>,
     ) -> Self {
         Self {
             client_tcp_stream,
@@ -71,6 +108,8 @@ where
             client_socket_addr,
             config,
             proxy_connection_factory,
+            resolver,
+            metrics_registry,
         }
     }
 
@@ -85,6 +124,27 @@ where
         let http_message = http_framed.next().await.ok_or(AgentError::Other(format!(
             "Nothing to read from client: {client_socket_addr}"
         )))??;
+
+        match extract_basic_credential(&http_message) {
+            Some((username, password)) if self.config.verify_client_credential(&username, &password) => {}
+            _ => {
+                debug!("Client http connection [{client_socket_addr}] fail proxy authentication.");
+                let mut proxy_auth_required_response = Response::new(
+                    HttpVersion::V1_1,
+                    StatusCode::new(PROXY_AUTH_REQUIRED_CODE).unwrap(),
+                    ReasonPhrase::new(PROXY_AUTH_REQUIRED_REASON).unwrap(),
+                    vec![],
+                );
+                proxy_auth_required_response
+                    .header_mut()
+                    .add_field(PROXY_AUTHENTICATE_HEADER, BASIC_AUTH_SCHEME);
+                http_framed.send(proxy_auth_required_response).await?;
+                return Err(AgentError::Other(format!(
+                    "Client http connection [{client_socket_addr}] fail proxy authentication."
+                )));
+            }
+        }
+
         let http_method = http_message.method().to_string().to_lowercase();
         let (request_url, init_data) = if http_method == CONNECT_METHOD {
             (
@@ -130,9 +190,21 @@ where
                 "0.0.0.1 or 127.0.0.1 is not a valid destination address: {target_host}"
             )));
         }
-        let dst_address = PpaassUnifiedAddress::Domain {
-            host: target_host,
-            port: target_port,
+        let dst_address = if self.config.resolve_at_agent() {
+            match self.resolver.resolve(&target_host).await? {
+                Some(resolved_ip) => {
+                    PpaassUnifiedAddress::Ip(SocketAddr::new(resolved_ip, target_port))
+                }
+                None => PpaassUnifiedAddress::Domain {
+                    host: target_host,
+                    port: target_port,
+                },
+            }
+        } else {
+            PpaassUnifiedAddress::Domain {
+                host: target_host,
+                port: target_port,
+            }
         };
 
         let user_token = self.config.user_token();
@@ -145,10 +217,23 @@ where
             payload_encryption.clone(),
         )?;
 
-        let proxy_connection = self
+        let mut proxy_connection = self
             .proxy_connection_factory
             .create_proxy_connection()
             .await?;
+        if self.config.proxy_protocol_version() != ProxyProtocolVersion::Off {
+            let ppaass_proxy_addr = resolve_ppaass_proxy_addr(&self.config).await?;
+            if let Some(proxy_protocol_preamble) = build_preamble(
+                self.config.proxy_protocol_version(),
+                client_socket_addr,
+                ppaass_proxy_addr,
+            ) {
+                proxy_connection
+                    .get_mut()
+                    .write_all(&proxy_protocol_preamble)
+                    .await?;
+            }
+        }
         let (mut proxy_connection_write, mut proxy_connection_read) = proxy_connection.split();
         debug!("Client tcp connection [{src_address}] success to create proxy connection.",);
         proxy_connection_write.send(tcp_init_request).await?;
@@ -192,14 +277,17 @@ where
             );
             http_framed.send(http_connect_success_response).await?;
         }
+        let (upload_bytes_amount, download_bytes_amount) = self
+            .metrics_registry
+            .register(transport_id.clone(), src_address.to_string());
         let FramedParts {
             io: client_tcp_stream,
             ..
         } = http_framed.into_parts();
-        tcp_relay(
+        let relay_result = tcp_relay(
             &self.config,
             ClientTransportTcpDataRelay {
-                transport_id,
+                transport_id: transport_id.clone(),
                 client_tcp_stream,
                 src_address,
                 dst_address,
@@ -207,8 +295,12 @@ where
                 proxy_connection_read,
                 init_data,
                 payload_encryption,
+                upload_bytes_amount,
+                download_bytes_amount,
             },
         )
-        .await
+        .await;
+        self.metrics_registry.deregister(&transport_id);
+        relay_result
     }
 }
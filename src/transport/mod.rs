@@ -0,0 +1,117 @@
+pub(crate) mod dispatcher;
+pub(crate) mod http;
+pub(crate) mod proxy_protocol;
+pub(crate) mod socks5;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use ppaass_crypto::crypto::RsaCryptoFetcher;
+use ppaass_protocol::generator::PpaassMessageGenerator;
+use ppaass_protocol::message::values::address::PpaassUnifiedAddress;
+use ppaass_protocol::message::values::encryption::PpaassMessagePayloadEncryption;
+use ppaass_protocol::message::PpaassProxyMessage;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tracing::{debug, error};
+
+use crate::{
+    config::AgentConfig,
+    error::AgentError,
+    proxy::{ProxyConnectionRead, ProxyConnectionWrite},
+};
+
+const RELAY_BUFFER_SIZE: usize = 65536;
+
+/// Everything needed to pump bytes between the client socket and the
+/// already-initialized proxy tunnel for a single transport.
+pub(crate) struct ClientTransportTcpDataRelay<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    pub transport_id: String,
+    pub client_tcp_stream: TcpStream,
+    pub src_address: PpaassUnifiedAddress,
+    pub dst_address: PpaassUnifiedAddress,
+    pub proxy_connection_write: ProxyConnectionWrite<F>,
+    pub proxy_connection_read: ProxyConnectionRead<F>,
+    pub init_data: Option<Bytes>,
+    pub payload_encryption: PpaassMessagePayloadEncryption,
+    pub upload_bytes_amount: Arc<AtomicU64>,
+    pub download_bytes_amount: Arc<AtomicU64>,
+}
+
+/// Relays bytes between the client tcp stream and the proxy connection until
+/// both directions have finished (upload when the client closes, download
+/// when the proxy connection closes) or either direction fails.
+pub(crate) async fn tcp_relay<F>(
+    _config: &AgentConfig,
+    relay: ClientTransportTcpDataRelay<F>,
+) -> Result<(), AgentError>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    let ClientTransportTcpDataRelay {
+        transport_id,
+        client_tcp_stream,
+        src_address,
+        dst_address,
+        mut proxy_connection_write,
+        mut proxy_connection_read,
+        init_data,
+        payload_encryption,
+        upload_bytes_amount,
+        download_bytes_amount,
+    } = relay;
+    let (mut client_tcp_read, mut client_tcp_write) = client_tcp_stream.into_split();
+
+    if let Some(init_data) = init_data {
+        let agent_message = PpaassMessageGenerator::generate_agent_tcp_data_message(
+            transport_id.clone(),
+            payload_encryption.clone(),
+            init_data,
+        )?;
+        proxy_connection_write.send(agent_message).await?;
+    }
+
+    let upload_task = async move {
+        let mut buf = vec![0u8; RELAY_BUFFER_SIZE];
+        loop {
+            let size = client_tcp_read.read(&mut buf).await?;
+            if size == 0 {
+                debug!("Client tcp connection [{src_address}] closed, stop upload.");
+                return Ok::<(), AgentError>(());
+            }
+            upload_bytes_amount.fetch_add(size as u64, Ordering::Relaxed);
+            let agent_message = PpaassMessageGenerator::generate_agent_tcp_data_message(
+                transport_id.clone(),
+                payload_encryption.clone(),
+                Bytes::copy_from_slice(&buf[..size]),
+            )?;
+            proxy_connection_write.send(agent_message).await?;
+        }
+    };
+
+    let download_task = async move {
+        while let Some(proxy_message) = proxy_connection_read.next().await {
+            let PpaassProxyMessage { payload, .. } = proxy_message?;
+            let data = match payload {
+                ppaass_protocol::message::PpaassProxyMessagePayload::Tcp(
+                    ppaass_protocol::message::payload::tcp::ProxyTcpPayload::Data { data, .. },
+                ) => data,
+                _ => continue,
+            };
+            download_bytes_amount.fetch_add(data.len() as u64, Ordering::Relaxed);
+            client_tcp_write.write_all(&data).await?;
+        }
+        debug!("Proxy connection for [{dst_address}] closed, stop download.");
+        Ok::<(), AgentError>(())
+    };
+
+    if let Err(e) = tokio::try_join!(upload_task, download_task) {
+        error!("Tcp relay fail to forward data because of error: {e:?}");
+    }
+    Ok(())
+}
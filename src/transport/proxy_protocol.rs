@@ -0,0 +1,78 @@
+use std::net::SocketAddr;
+
+use bytes::{BufMut, Bytes, BytesMut};
+use serde::{Deserialize, Serialize};
+
+const V2_SIGNATURE: [u8; 12] = [
+    0x0D, 0x0A, 0x0D, 0x0A, 0x00, 0x0D, 0x0A, 0x51, 0x55, 0x49, 0x54, 0x0A,
+];
+const V2_VERSION_COMMAND: u8 = 0x21;
+const V2_FAMILY_TCP_OVER_IPV4: u8 = 0x11;
+const V2_FAMILY_TCP_OVER_IPV6: u8 = 0x21;
+
+/// Which PROXY protocol preamble, if any, to prepend to a freshly
+/// established proxy connection so the downstream proxy learns the real
+/// client address.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProxyProtocolVersion {
+    #[default]
+    Off,
+    V1,
+    V2,
+}
+
+/// Builds the PROXY protocol preamble for `src_addr` (the real client
+/// socket address) talking to `dst_addr` (the ppaass proxy's address),
+/// or `None` when the protocol is off or the two addresses are mixed
+/// IPv4/IPv6 families, which PROXY protocol cannot represent.
+pub(crate) fn build_preamble(
+    version: ProxyProtocolVersion,
+    src_addr: SocketAddr,
+    dst_addr: SocketAddr,
+) -> Option<Bytes> {
+    if src_addr.is_ipv4() != dst_addr.is_ipv4() {
+        return None;
+    }
+    match version {
+        ProxyProtocolVersion::Off => None,
+        ProxyProtocolVersion::V1 => Some(build_v1_preamble(src_addr, dst_addr)),
+        ProxyProtocolVersion::V2 => Some(build_v2_preamble(src_addr, dst_addr)),
+    }
+}
+
+fn build_v1_preamble(src_addr: SocketAddr, dst_addr: SocketAddr) -> Bytes {
+    let protocol_family = if src_addr.is_ipv4() { "TCP4" } else { "TCP6" };
+    Bytes::from(format!(
+        "PROXY {protocol_family} {} {} {} {}\r\n",
+        src_addr.ip(),
+        dst_addr.ip(),
+        src_addr.port(),
+        dst_addr.port()
+    ))
+}
+
+fn build_v2_preamble(src_addr: SocketAddr, dst_addr: SocketAddr) -> Bytes {
+    let mut preamble = BytesMut::new();
+    preamble.put_slice(&V2_SIGNATURE);
+    preamble.put_u8(V2_VERSION_COMMAND);
+    match (src_addr, dst_addr) {
+        (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+            preamble.put_u8(V2_FAMILY_TCP_OVER_IPV4);
+            preamble.put_u16(4 + 4 + 2 + 2);
+            preamble.put_slice(&src.ip().octets());
+            preamble.put_slice(&dst.ip().octets());
+            preamble.put_u16(src.port());
+            preamble.put_u16(dst.port());
+        }
+        (SocketAddr::V6(src), SocketAddr::V6(dst)) => {
+            preamble.put_u8(V2_FAMILY_TCP_OVER_IPV6);
+            preamble.put_u16(16 + 16 + 2 + 2);
+            preamble.put_slice(&src.ip().octets());
+            preamble.put_slice(&dst.ip().octets());
+            preamble.put_u16(src.port());
+            preamble.put_u16(dst.port());
+        }
+        _ => unreachable!("mixed address families are filtered out by build_preamble"),
+    }
+    preamble.freeze()
+}
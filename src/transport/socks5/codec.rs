@@ -0,0 +1,106 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use bytes::{Buf, BufMut, BytesMut};
+use ppaass_protocol::message::values::address::PpaassUnifiedAddress;
+use tokio_util::codec::{Decoder, Encoder};
+
+use crate::error::AgentError;
+
+const SOCKS5_VERSION: u8 = 0x05;
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+const REPLY_SUCCESS: u8 = 0x00;
+
+pub(crate) enum Socks5Command {
+    Connect { dst_address: PpaassUnifiedAddress },
+}
+
+impl Socks5Command {
+    pub(crate) fn connect_success_reply() -> Self {
+        Self::Connect {
+            dst_address: PpaassUnifiedAddress::Ip(std::net::SocketAddr::new(
+                IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)),
+                0,
+            )),
+        }
+    }
+}
+
+/// Decodes the SOCKS5 connect request that follows method negotiation /
+/// authentication, and encodes the reply sent back to the client.
+#[derive(Default)]
+pub(crate) struct Socks5Codec;
+
+impl Decoder for Socks5Codec {
+    type Item = Socks5Command;
+    type Error = AgentError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+        let atyp = src[3];
+        let dst_address = match atyp {
+            ATYP_IPV4 => {
+                if src.len() < 10 {
+                    return Ok(None);
+                }
+                src.advance(4);
+                let ip = Ipv4Addr::new(src.get_u8(), src.get_u8(), src.get_u8(), src.get_u8());
+                let port = src.get_u16();
+                PpaassUnifiedAddress::Ip(std::net::SocketAddr::new(IpAddr::V4(ip), port))
+            }
+            ATYP_DOMAIN => {
+                if src.len() < 5 {
+                    return Ok(None);
+                }
+                let domain_len = src[4] as usize;
+                if src.len() < 5 + domain_len + 2 {
+                    return Ok(None);
+                }
+                src.advance(5);
+                let domain = String::from_utf8_lossy(&src[..domain_len]).to_string();
+                src.advance(domain_len);
+                let port = src.get_u16();
+                PpaassUnifiedAddress::Domain {
+                    host: domain,
+                    port,
+                }
+            }
+            ATYP_IPV6 => {
+                if src.len() < 22 {
+                    return Ok(None);
+                }
+                src.advance(4);
+                let mut octets = [0u8; 16];
+                src.copy_to_slice(&mut octets);
+                let port = src.get_u16();
+                PpaassUnifiedAddress::Ip(std::net::SocketAddr::new(
+                    IpAddr::V6(Ipv6Addr::from(octets)),
+                    port,
+                ))
+            }
+            _ => {
+                return Err(AgentError::Other(format!(
+                    "Unsupported socks5 address type: {atyp}"
+                )))
+            }
+        };
+        Ok(Some(Socks5Command::Connect { dst_address }))
+    }
+}
+
+impl Encoder<Socks5Command> for Socks5Codec {
+    type Error = AgentError;
+
+    fn encode(&mut self, _item: Socks5Command, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.put_u8(SOCKS5_VERSION);
+        dst.put_u8(REPLY_SUCCESS);
+        dst.put_u8(0x00);
+        dst.put_u8(ATYP_IPV4);
+        dst.put_slice(&[0, 0, 0, 0]);
+        dst.put_u16(0);
+        Ok(())
+    }
+}
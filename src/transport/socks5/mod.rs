@@ -0,0 +1,260 @@
+mod codec;
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::{SinkExt, StreamExt};
+use ppaass_crypto::{crypto::RsaCryptoFetcher, random_32_bytes};
+use ppaass_protocol::generator::PpaassMessageGenerator;
+use ppaass_protocol::message::payload::tcp::{ProxyTcpInitResult, ProxyTcpPayload};
+use ppaass_protocol::message::values::address::PpaassUnifiedAddress;
+use ppaass_protocol::message::values::encryption::PpaassMessagePayloadEncryptionSelector;
+use ppaass_protocol::message::{PpaassProxyMessage, PpaassProxyMessagePayload};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio_util::codec::{Framed, FramedParts};
+use tracing::{debug, error};
+
+use self::codec::{Socks5Codec, Socks5Command};
+use crate::{
+    config::AgentConfig, crypto::AgentServerPayloadEncryptionTypeSelector,
+    error::AgentError, metrics::MetricsRegistry,
+    proxy::{resolve_ppaass_proxy_addr, ProxyConnectionFactory},
+    resolver::AgentResolver,
+    transport::proxy_protocol::{build_preamble, ProxyProtocolVersion},
+    transport::tcp_relay,
+    transport::ClientTransportTcpDataRelay,
+};
+
+const SOCKS5_VERSION: u8 = 0x05;
+const METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const SUBNEGOTIATION_VERSION: u8 = 0x01;
+const SUBNEGOTIATION_SUCCESS: u8 = 0x00;
+const SUBNEGOTIATION_FAILURE: u8 = 0x01;
+
+/// Tops `leftover` up from the socket until it holds at least `len` bytes,
+/// then splits those bytes off the front. Used while parsing the SOCKS5
+/// greeting and username/password sub-negotiation, both of which happen
+/// before a `Framed` codec is attached to the stream.
+async fn read_handshake_bytes(
+    client_tcp_stream: &mut TcpStream,
+    leftover: &mut BytesMut,
+    len: usize,
+) -> Result<Bytes, AgentError> {
+    while leftover.len() < len {
+        let mut chunk = [0u8; 512];
+        let size = client_tcp_stream.read(&mut chunk).await?;
+        if size == 0 {
+            return Err(AgentError::Other(
+                "Client closed connection during socks5 handshake".to_string(),
+            ));
+        }
+        leftover.extend_from_slice(&chunk[..size]);
+    }
+    Ok(leftover.split_to(len).freeze())
+}
+
+pub(crate) struct Socks5ClientTransport<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    client_tcp_stream: TcpStream,
+    src_address: PpaassUnifiedAddress,
+    initial_buf: BytesMut,
+    client_socket_addr: SocketAddr,
+    config: Arc<AgentConfig>,
+    proxy_connection_factory: Arc<ProxyConnectionFactory<F>>,
+    resolver: Arc<AgentResolver>,
+    metrics_registry: Arc<MetricsRegistry>,
+}
+
+impl<F> Socks5ClientTransport<F>
+where
+    F: RsaCryptoFetcher + Send + Sync + 'static,
+{
+    pub(crate) fn new(
+        client_tcp_stream: TcpStream,
+        src_address: PpaassUnifiedAddress,
+        initial_buf: BytesMut,
+        client_socket_addr: SocketAddr,
+        config: Arc<AgentConfig>,
+        proxy_connection_factory: Arc<ProxyConnectionFactory<F>>,
+        resolver: Arc<AgentResolver>,
+        metrics_registry: Arc<MetricsRegistry>,
+    ) -> Self {
+        Self {
+            client_tcp_stream,
+            src_address,
+            initial_buf,
+            client_socket_addr,
+            config,
+            proxy_connection_factory,
+            resolver,
+            metrics_registry,
+        }
+    }
+
+    /// Reads the SOCKS5 method negotiation greeting, always advertises
+    /// username/password auth (RFC 1929 method `0x02`), and replies.
+    async fn negotiate_method(
+        client_tcp_stream: &mut TcpStream,
+        leftover: &mut BytesMut,
+    ) -> Result<(), AgentError> {
+        let greeting_header = read_handshake_bytes(client_tcp_stream, leftover, 2).await?;
+        let method_count = greeting_header[1] as usize;
+        read_handshake_bytes(client_tcp_stream, leftover, method_count).await?;
+        client_tcp_stream
+            .write_all(&[SOCKS5_VERSION, METHOD_USERNAME_PASSWORD])
+            .await?;
+        Ok(())
+    }
+
+    /// Reads the RFC 1929 username/password sub-negotiation frame and
+    /// verifies it against the configured bcrypt credential store.
+    async fn authenticate(
+        config: &AgentConfig,
+        client_tcp_stream: &mut TcpStream,
+        leftover: &mut BytesMut,
+        src_address: &PpaassUnifiedAddress,
+    ) -> Result<(), AgentError> {
+        let sub_negotiation_header = read_handshake_bytes(client_tcp_stream, leftover, 2).await?;
+        let username_len = sub_negotiation_header[1] as usize;
+        let username_bytes = read_handshake_bytes(client_tcp_stream, leftover, username_len).await?;
+        let password_len_byte = read_handshake_bytes(client_tcp_stream, leftover, 1).await?;
+        let password_len = password_len_byte[0] as usize;
+        let password_bytes = read_handshake_bytes(client_tcp_stream, leftover, password_len).await?;
+        let username = String::from_utf8_lossy(&username_bytes).to_string();
+        let password = String::from_utf8_lossy(&password_bytes).to_string();
+
+        if !config.verify_client_credential(&username, &password) {
+            client_tcp_stream
+                .write_all(&[SUBNEGOTIATION_VERSION, SUBNEGOTIATION_FAILURE])
+                .await?;
+            client_tcp_stream.shutdown().await?;
+            return Err(AgentError::Other(format!(
+                "Client socks5 connection [{src_address}] fail socks5 authentication for user [{username}]"
+            )));
+        }
+        client_tcp_stream
+            .write_all(&[SUBNEGOTIATION_VERSION, SUBNEGOTIATION_SUCCESS])
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn process(self) -> Result<(), AgentError> {
+        let src_address = self.src_address;
+        let client_socket_addr = self.client_socket_addr;
+        let mut client_tcp_stream = self.client_tcp_stream;
+        let mut leftover = self.initial_buf;
+
+        Self::negotiate_method(&mut client_tcp_stream, &mut leftover).await?;
+        Self::authenticate(&self.config, &mut client_tcp_stream, &mut leftover, &src_address).await?;
+
+        let mut framed_parts = FramedParts::new(client_tcp_stream, Socks5Codec::default());
+        framed_parts.read_buf = leftover;
+        let mut socks5_framed = Framed::from_parts(framed_parts);
+
+        let request = socks5_framed.next().await.ok_or(AgentError::Other(format!(
+            "Nothing to read from client: {client_socket_addr}"
+        )))??;
+        let Socks5Command::Connect { dst_address } = request;
+        let dst_address = match (self.config.resolve_at_agent(), &dst_address) {
+            (true, PpaassUnifiedAddress::Domain { host, port }) => {
+                match self.resolver.resolve(host).await? {
+                    Some(resolved_ip) => PpaassUnifiedAddress::Ip(SocketAddr::new(resolved_ip, *port)),
+                    None => dst_address,
+                }
+            }
+            _ => dst_address,
+        };
+
+        let user_token = self.config.user_token();
+        let payload_encryption =
+            AgentServerPayloadEncryptionTypeSelector::select(user_token, Some(random_32_bytes()));
+        let tcp_init_request = PpaassMessageGenerator::generate_agent_tcp_init_message(
+            user_token.to_string(),
+            src_address.clone(),
+            dst_address.clone(),
+            payload_encryption.clone(),
+        )?;
+
+        let mut proxy_connection = self
+            .proxy_connection_factory
+            .create_proxy_connection()
+            .await?;
+        if self.config.proxy_protocol_version() != ProxyProtocolVersion::Off {
+            let ppaass_proxy_addr = resolve_ppaass_proxy_addr(&self.config).await?;
+            if let Some(proxy_protocol_preamble) = build_preamble(
+                self.config.proxy_protocol_version(),
+                client_socket_addr,
+                ppaass_proxy_addr,
+            ) {
+                proxy_connection
+                    .get_mut()
+                    .write_all(&proxy_protocol_preamble)
+                    .await?;
+            }
+        }
+        let (mut proxy_connection_write, mut proxy_connection_read) = proxy_connection.split();
+        debug!("Client socks5 connection [{src_address}] success to create proxy connection.");
+        proxy_connection_write.send(tcp_init_request).await?;
+
+        let proxy_message = proxy_connection_read
+            .next()
+            .await
+            .ok_or(AgentError::Other(format!(
+                "Nothing to read from proxy for client: {client_socket_addr}"
+            )))??;
+        let PpaassProxyMessage {
+            payload: proxy_message_payload,
+            ..
+        } = proxy_message;
+        let PpaassProxyMessagePayload::Tcp(ProxyTcpPayload::Init { result, .. }) =
+            proxy_message_payload
+        else {
+            return Err(AgentError::Other(format!(
+                "Not a tcp init response for client {client_socket_addr}."
+            )));
+        };
+        let transport_id = match result {
+            ProxyTcpInitResult::Success(transport_id) => transport_id,
+            ProxyTcpInitResult::Fail(reason) => {
+                error!("Client socks5 tcp connection [{src_address}] fail to initialize tcp connection with proxy because of reason: {reason:?}");
+                return Err(AgentError::Other(format!(
+                    "Client socks5 tcp connection [{src_address}] fail to initialize tcp connection with proxy because of reason: {reason:?}"
+                )));
+            }
+        };
+        debug!("Client socks5 tcp connection [{src_address}] success to initialize tcp connection with proxy on tunnel: {transport_id}");
+        socks5_framed
+            .send(Socks5Command::connect_success_reply())
+            .await?;
+        let (upload_bytes_amount, download_bytes_amount) = self
+            .metrics_registry
+            .register(transport_id.clone(), src_address.to_string());
+
+        let FramedParts {
+            io: client_tcp_stream,
+            ..
+        } = socks5_framed.into_parts();
+        let relay_result = tcp_relay(
+            &self.config,
+            ClientTransportTcpDataRelay {
+                transport_id: transport_id.clone(),
+                client_tcp_stream,
+                src_address,
+                dst_address,
+                proxy_connection_write,
+                proxy_connection_read,
+                init_data: None,
+                payload_encryption,
+                upload_bytes_amount,
+                download_bytes_amount,
+            },
+        )
+        .await;
+        self.metrics_registry.deregister(&transport_id);
+        relay_result
+    }
+}